@@ -23,7 +23,8 @@
 
 use core::fmt::Write;
 use core::convert::Into;
-use core::str::from_utf8_unchecked;
+use core::str::{from_utf8_unchecked, Utf8Error};
+use core::char::{decode_utf16, REPLACEMENT_CHARACTER};
 
 pub struct ByteString<const N: usize> {
     buf:    [u8; N],
@@ -45,6 +46,53 @@ impl<const N: usize> ByteString<N> {
         }
     }
 
+    /// Build a string buffer from validated UTF-8 bytes, copying as many chars as fit
+    pub fn from_utf8(bytes: &[u8]) -> Result<Self, Utf8Error> {
+        let s = core::str::from_utf8(bytes)?;
+        let mut sb = Self::new();
+        for c in s.chars() {
+            if !sb.push(c) { break }
+        }
+        Ok(sb)
+    }
+
+    /// Build a string buffer from UTF-8 bytes, replacing invalid sequences with U+FFFD
+    pub fn from_utf8_lossy(bytes: &[u8]) -> Self {
+        let mut sb = Self::new();
+        let mut rest = bytes;
+        while !rest.is_empty() && !sb.is_full() {
+            match core::str::from_utf8(rest) {
+                Ok(s) => {
+                    for c in s.chars() {
+                        if !sb.push(c) { break }
+                    }
+                    break
+                }
+                Err(e) => {
+                    let valid = unsafe { core::str::from_utf8_unchecked(&rest[..e.valid_up_to()]) };
+                    for c in valid.chars() {
+                        if !sb.push(c) { return sb }
+                    }
+                    if sb.is_full() { break }
+                    if !sb.push(REPLACEMENT_CHARACTER) { break }
+                    let invalid_len = e.error_len().unwrap_or(rest.len() - e.valid_up_to());
+                    rest = &rest[e.valid_up_to() + invalid_len..];
+                }
+            }
+        }
+        sb
+    }
+
+    /// Build a string buffer from UTF-16 code units, replacing invalid ones with U+FFFD
+    pub fn from_utf16(v: &[u16]) -> Self {
+        let mut sb = Self::new();
+        for c in decode_utf16(v.iter().copied()) {
+            let c = c.unwrap_or(REPLACEMENT_CHARACTER);
+            if !sb.push(c) { break }
+        }
+        sb
+    }
+
     /// Get the length of the string
     #[inline]
     pub fn len(&self) -> usize {
@@ -57,6 +105,18 @@ impl<const N: usize> ByteString<N> {
         self.buf.len()
     }
 
+    /// Get the number of bytes still free in the buffer
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.buf.len() - self.pos
+    }
+
+    /// Test if the buffer has no room left
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.pos == self.buf.len()
+    }
+
     /// Test if the string is empty
     #[inline]
     pub fn is_empty(&self) -> bool {
@@ -75,15 +135,21 @@ impl<const N: usize> ByteString<N> {
         &self.buf[0..self.pos]
     }
 
-    /// Get the char at position in the string
+    /// Get the char at a given byte offset, or `None` if not a char boundary
     pub fn char_at(&self, pos: usize) -> Option<char> {
-        if pos < self.pos {
-            Some(self.buf[pos] as char)
+        let s = self.str();
+        if s.is_char_boundary(pos) {
+            s[pos..].chars().next()
         } else {
             None
         }
     }
 
+    /// Iterate over the `char`s of the string
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.str().chars()
+    }
+
     /// Append a byte to the string
     pub fn append(&mut self, b: u8) {
         if self.pos < self.buf.len() {
@@ -92,11 +158,33 @@ impl<const N: usize> ByteString<N> {
         }
     }
 
-    /// Append a string to the string buffer
+    /// Append a string to the string buffer (silently truncates on overflow)
     pub fn append_str(&mut self, s: &str) {
         for b in s.bytes() { self.append(b); }
     }
- 
+
+    /// Append a string to the buffer, or return an error if it doesn't fit
+    pub fn try_push_str(&mut self, s: &str) -> Result<(), CapacityError> {
+        let bytes = s.as_bytes();
+        if bytes.len() > self.remaining() {
+            return Err(CapacityError { overflow: bytes.len() - self.remaining() })
+        }
+        for b in bytes { self.append(*b); }
+        Ok(())
+    }
+
+    /// Push a single char to the string buffer, or return `false` if it doesn't fit
+    pub fn push(&mut self, c: char) -> bool {
+        let mut tmp = [0u8; 4];
+        let encoded = c.encode_utf8(&mut tmp);
+        let bytes = encoded.as_bytes();
+        if bytes.len() > self.buf.len() - self.pos {
+            return false
+        }
+        for b in bytes { self.append(*b); }
+        true
+    }
+
     /// convert string to string buffer
     pub fn from_str(&mut self, s: &str) {
         self.clear();
@@ -111,6 +199,66 @@ impl<const N: usize> ByteString<N> {
         false
     }
 
+    /// Find the first occurrence of `needle`, if any
+    pub fn find(&self, needle: &[u8]) -> Option<usize> {
+        let hay = self.slice();
+        if needle.is_empty() { return Some(0) }
+        if needle.len() > hay.len() { return None }
+        let first = needle[0];
+        let mut i = 0;
+        while i <= hay.len() - needle.len() {
+            match hay[i..].iter().position(|&b| b == first) {
+                Some(skip) => {
+                    i += skip;
+                    if i > hay.len() - needle.len() { return None }
+                    if &hay[i..i + needle.len()] == needle {
+                        return Some(i)
+                    }
+                    i += 1;
+                }
+                None => return None,
+            }
+        }
+        None
+    }
+
+    /// Find the last occurrence of `needle`, if any
+    pub fn rfind(&self, needle: &[u8]) -> Option<usize> {
+        let hay = self.slice();
+        if needle.is_empty() { return Some(hay.len()) }
+        if needle.len() > hay.len() { return None }
+        let mut i = hay.len() - needle.len();
+        loop {
+            if &hay[i..i + needle.len()] == needle {
+                return Some(i)
+            }
+            if i == 0 { return None }
+            i -= 1;
+        }
+    }
+
+    /// Test if the string starts with `needle`
+    pub fn starts_with(&self, needle: &[u8]) -> bool {
+        let hay = self.slice();
+        needle.len() <= hay.len() && &hay[..needle.len()] == needle
+    }
+
+    /// Test if the string ends with `needle`
+    pub fn ends_with(&self, needle: &[u8]) -> bool {
+        let hay = self.slice();
+        needle.len() <= hay.len() && &hay[hay.len() - needle.len()..] == needle
+    }
+
+    /// Test if the string contains `needle`
+    pub fn contains_str(&self, needle: &str) -> bool {
+        self.find(needle.as_bytes()).is_some()
+    }
+
+    /// Split the string on occurrences of the delimiter byte `b`
+    pub fn split_byte(&self, b: u8) -> impl Iterator<Item = &[u8]> {
+        self.slice().split(move |&x| x == b)
+    }
+
     /// Delete last byte of the string
     pub fn del_last(&mut self) {
         if self.pos > 0 {
@@ -128,6 +276,83 @@ impl<const N: usize> ByteString<N> {
         }
     }
 
+    /// Remove and return the last char of the string
+    ///
+    /// Returns `None` if the string is empty. Any bytes after the longest
+    /// valid UTF-8 prefix are dropped along with the char.
+    pub fn pop(&mut self) -> Option<char> {
+        let c = self.str().chars().next_back()?;
+        self.pos = self.str().len() - c.len_utf8();
+        Some(c)
+    }
+
+    /// Shorten the string to `new_len` bytes
+    ///
+    /// Panics if `new_len` is not a char boundary. Does nothing if
+    /// `new_len` is greater than the current length.
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len >= self.pos {
+            return
+        }
+        assert!(self.str().is_char_boundary(new_len), "new_len is not a char boundary");
+        self.pos = new_len;
+    }
+
+    /// Insert a char at byte offset `idx`, shifting the rest right
+    ///
+    /// Returns `false` without modifying the buffer if `c` does not fit
+    /// in the remaining capacity. Panics if `idx` is not a char boundary.
+    pub fn insert(&mut self, idx: usize, c: char) -> bool {
+        let mut tmp = [0u8; 4];
+        self.insert_str(idx, c.encode_utf8(&mut tmp))
+    }
+
+    /// Insert a string at byte offset `idx`, shifting the rest right
+    ///
+    /// Returns `false` without modifying the buffer if `s` does not fit
+    /// in the remaining capacity. Panics if `idx` is not a char boundary.
+    pub fn insert_str(&mut self, idx: usize, s: &str) -> bool {
+        assert!(self.str().is_char_boundary(idx), "idx is not a char boundary");
+        let bytes = s.as_bytes();
+        if bytes.len() > self.remaining() {
+            return false
+        }
+        self.buf.copy_within(idx..self.pos, idx + bytes.len());
+        self.buf[idx..idx + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+        true
+    }
+
+    /// Remove and return the char at byte offset `idx`, shifting the rest left
+    ///
+    /// Panics if `idx` is not a char boundary or is out of range.
+    pub fn remove(&mut self, idx: usize) -> char {
+        let c = self.str()[idx..].chars().next().expect("idx out of bounds");
+        let next = idx + c.len_utf8();
+        self.buf.copy_within(next..self.pos, idx);
+        self.pos -= c.len_utf8();
+        c
+    }
+
+    /// Replace the byte range `range` with `replace_with`
+    ///
+    /// Returns `false` without modifying the buffer if the replacement
+    /// does not fit in the remaining capacity. Panics if the range's
+    /// bounds are not char boundaries.
+    pub fn replace_range(&mut self, range: core::ops::Range<usize>, replace_with: &str) -> bool {
+        let s = self.str();
+        assert!(s.is_char_boundary(range.start) && s.is_char_boundary(range.end), "range is not on char boundaries");
+        let new_bytes = replace_with.as_bytes();
+        let old_len = range.end - range.start;
+        if new_bytes.len() > old_len && new_bytes.len() - old_len > self.remaining() {
+            return false
+        }
+        self.buf.copy_within(range.end..self.pos, range.start + new_bytes.len());
+        self.buf[range.start..range.start + new_bytes.len()].copy_from_slice(new_bytes);
+        self.pos = self.pos - old_len + new_bytes.len();
+        true
+    }
+
     /// Convert string buffer to string
     #[inline]
     pub fn str(&self) -> &str {
@@ -137,15 +362,91 @@ impl<const N: usize> ByteString<N> {
 
 
 impl<const N: usize> Write for ByteString<N> {
+    /// Returns `Err` if `s` does not fully fit, instead of truncating it
     fn write_str(&mut self, s: &str) -> Result<(), core::fmt::Error> {
-        for b in s.bytes() { self.append(b); }
-        Ok(())
+        self.try_push_str(s).map_err(|_| core::fmt::Error)
     }
 }
 
+/// Error returned when a write would not fit in the remaining capacity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError {
+    /// Number of bytes that did not fit
+    pub overflow: usize,
+}
+
 impl<'a, const N: usize> Into<&'a str> for &'a ByteString<N> {
+    // `append(u8)` can leave the buffer holding invalid UTF-8, so this falls
+    // back to the longest valid prefix instead of trusting it blindly.
     fn into(self) -> &'a str {
-        unsafe { from_utf8_unchecked(self.slice()) }
+        match core::str::from_utf8(self.slice()) {
+            Ok(s) => s,
+            Err(e) => unsafe { from_utf8_unchecked(&self.slice()[..e.valid_up_to()]) },
+        }
+    }
+}
+
+impl<const N: usize> core::ops::Deref for ByteString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.str()
+    }
+}
+
+impl<const N: usize> core::fmt::Display for ByteString<N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(self.str(), f)
+    }
+}
+
+impl<const N: usize> core::fmt::Debug for ByteString<N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.str(), f)
+    }
+}
+
+impl<const N: usize> PartialEq for ByteString<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.str() == other.str()
+    }
+}
+
+impl<const N: usize> Eq for ByteString<N> {}
+
+impl<const N: usize> PartialEq<str> for ByteString<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.str() == other
+    }
+}
+
+impl<const N: usize> PartialEq<&str> for ByteString<N> {
+    fn eq(&self, other: &&str) -> bool {
+        self.str() == *other
+    }
+}
+
+impl<const N: usize> PartialEq<ByteString<N>> for str {
+    fn eq(&self, other: &ByteString<N>) -> bool {
+        self == other.str()
+    }
+}
+
+impl<const N: usize> core::hash::Hash for ByteString<N> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.str().hash(state)
+    }
+}
+
+impl<const N: usize> AsRef<str> for ByteString<N> {
+    fn as_ref(&self) -> &str {
+        self.str()
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for ByteString<N> {
+    fn as_ref(&self) -> &[u8] {
+        self.slice()
     }
 }
 
@@ -175,7 +476,7 @@ mod tests {
         let _ = write!(bs, "   ");
         bs.trim_end();
         assert_eq!(bs.len(), 3);
-        let _ = write!(bs, "12345678901234567890");
+        bs.append_str("12345678901234567890");
         assert_eq!(bs.len(), 20);
         bs.clear();
         assert_eq!(bs.len(), 0);
@@ -188,5 +489,143 @@ mod tests {
         bs.from_str("Hello");
         assert_eq!(bs.len(), 5);
         assert_eq!(bs.str(), "Hello");
+        assert_eq!(bs.remaining(), 15);
+        assert_eq!(bs.is_full(), false);
+        assert!(write!(bs, "1234567890123456").is_err());
+        assert_eq!(bs.str(), "Hello");
+        assert!(bs.try_push_str("1234567890123456").is_err());
+        assert_eq!(bs.str(), "Hello");
+        assert!(write!(bs, "123456789012345").is_ok());
+        assert_eq!(bs.is_full(), true);
+    }
+
+    #[test]
+    fn utf8_constructors() {
+        let sb = crate::ByteString::<10>::from_utf8("héllo".as_bytes()).unwrap();
+        assert_eq!(sb.str(), "héllo");
+        assert!(crate::ByteString::<10>::from_utf8(&[0xFF]).is_err());
+
+        // a multi-byte char that doesn't fit must not leave a lone byte behind
+        let truncated = crate::ByteString::<1>::from_utf8("é".as_bytes()).unwrap();
+        assert_eq!(truncated.len(), 0);
+        assert_eq!(truncated.str(), "");
+
+        let lossy = crate::ByteString::<10>::from_utf8_lossy(&[0x41, 0xFF, 0x42]);
+        assert_eq!(lossy.str(), "A\u{FFFD}B");
+        let lossy_truncated = crate::ByteString::<1>::from_utf8_lossy("é".as_bytes());
+        assert_eq!(lossy_truncated.len(), 0);
+
+        let utf16 = crate::ByteString::<10>::from_utf16(&[0x48, 0x69, 0xD800]);
+        assert_eq!(utf16.str(), "Hi\u{FFFD}");
+    }
+
+    #[test]
+    fn editing() {
+        let mut bs = crate::ByteString::<10>::new();
+        bs.from_str("Hello");
+        assert_eq!(bs.pop(), Some('o'));
+        assert_eq!(bs.str(), "Hell");
+        bs.truncate(2);
+        assert_eq!(bs.str(), "He");
+        assert!(bs.insert(1, 'i'));
+        assert_eq!(bs.str(), "Hie");
+        assert!(bs.insert_str(0, ">>"));
+        assert_eq!(bs.str(), ">>Hie");
+        assert_eq!(bs.remove(0), '>');
+        assert_eq!(bs.str(), ">Hie");
+        assert!(bs.replace_range(0..1, "An "));
+        assert_eq!(bs.str(), "An Hie");
+
+        // a buffer left holding a lone lead byte (via the byte-level
+        // `append`) must not be real UB to read back through these methods
+        let mut invalid = crate::ByteString::<1>::new();
+        invalid.append(0xC3);
+        assert_eq!(invalid.pop(), None);
+
+        // a bad lead byte followed by a valid one must not be real UB for
+        // char_at either, since the unchecked byte is before the valid part
+        let mut bad_lead = crate::ByteString::<4>::new();
+        bad_lead.append(0xFF);
+        bad_lead.append(b'x');
+        assert_eq!(bad_lead.char_at(0), None);
+    }
+
+    #[test]
+    fn trait_impls() {
+        use core::hash::{Hash, Hasher};
+
+        struct TestHasher(u64);
+        impl Hasher for TestHasher {
+            fn finish(&self) -> u64 { self.0 }
+            fn write(&mut self, bytes: &[u8]) {
+                for b in bytes { self.0 = self.0.wrapping_mul(31).wrapping_add(*b as u64); }
+            }
+        }
+
+        let mut a = crate::ByteString::<10>::new();
+        a.from_str("abc");
+        let mut b = crate::ByteString::<10>::new();
+        b.from_str("abc");
+
+        assert_eq!(&*a, "abc");
+        assert_eq!(a, b);
+
+        let mut out = crate::ByteString::<20>::new();
+        let _ = write!(out, "{}", a);
+        assert_eq!(out.str(), "abc");
+        out.clear();
+        let _ = write!(out, "{:?}", a);
+        assert_eq!(out.str(), "\"abc\"");
+        assert_eq!(a, "abc");
+        assert_eq!(*"abc", a);
+        assert_eq!(a.as_ref() as &str, "abc");
+        assert_eq!(a.as_ref() as &[u8], b"abc");
+
+        let mut h1 = TestHasher(0);
+        let mut h2 = TestHasher(0);
+        a.hash(&mut h1);
+        b.hash(&mut h2);
+        assert_eq!(h1.finish(), h2.finish());
+
+        b.push('d');
+        assert!(a != b);
+    }
+
+    #[test]
+    fn trait_impls_on_invalid_buffer() {
+        // a bad lead byte followed by a valid one must not panic or UB out
+        // of any trait impl built on `str()`
+        let mut bad_lead = crate::ByteString::<4>::new();
+        bad_lead.append(0xFF);
+        bad_lead.append(b'x');
+        assert_eq!(&*bad_lead, "");
+
+        let mut out = crate::ByteString::<20>::new();
+        let _ = write!(out, "{:?}", bad_lead);
+        assert_eq!(out.str(), "\"\"");
+    }
+
+    #[test]
+    fn byte_search() {
+        let mut bs = crate::ByteString::<20>::new();
+        bs.from_str("foo,bar,baz");
+
+        assert_eq!(bs.find(b"bar"), Some(4));
+        assert_eq!(bs.find(b"qux"), None);
+        assert_eq!(bs.find(b""), Some(0));
+        assert_eq!(bs.rfind(b"ba"), Some(8));
+        assert_eq!(bs.rfind(b"qux"), None);
+        assert!(bs.starts_with(b"foo"));
+        assert!(!bs.starts_with(b"bar"));
+        assert!(bs.ends_with(b"baz"));
+        assert!(!bs.ends_with(b"bar"));
+        assert!(bs.contains_str("bar"));
+        assert!(!bs.contains_str("qux"));
+
+        let mut parts = bs.split_byte(b',');
+        assert_eq!(parts.next(), Some(&b"foo"[..]));
+        assert_eq!(parts.next(), Some(&b"bar"[..]));
+        assert_eq!(parts.next(), Some(&b"baz"[..]));
+        assert_eq!(parts.next(), None);
     }
 }